@@ -13,14 +13,54 @@ use crate::rpc::pb::etcdserverpb::{
     LeaseKeepAliveResponse as PbLeaseKeepAliveResponse,
     LeaseLeasesRequest as PbLeaseLeasesRequest,
     LeaseLeasesResponse as PbLeaseLeasesResponse,
-    LeaseStatus};
+    LeaseStatus as PbLeaseStatus};
 
 use crate::rpc::ResponseHeader;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Channel;
-use tonic::{Interceptor, IntoRequest, Request};
-use tokio::sync::mpsc::channel;
+use tonic::{Interceptor, IntoRequest, Request, Streaming};
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
 use crate::Error;
 
+/// The ID of a lease.
+///
+/// Wraps the raw `i64` lease identifier so a lease id can't be accidentally swapped with a
+/// ttl at the call site, since both are plain `i64`s otherwise. Passing `LeaseId::AUTO` (or
+/// `0.into()`) to `lease_grant` asks the server to choose an id.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeaseId(i64);
+
+impl LeaseId {
+    /// Let the server choose the lease id when granting a lease.
+    pub const AUTO: Self = Self(0);
+}
+
+impl From<i64> for LeaseId {
+    #[inline]
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<LeaseId> for i64 {
+    #[inline]
+    fn from(id: LeaseId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for LeaseId {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}", self.0)
+    }
+}
+
 /// Client for lease operations.
 #[repr(transparent)]
 pub struct LeaseClient {
@@ -46,7 +86,7 @@ impl LeaseClient {
     pub async fn lease_grant(
         &mut self,
         ttl: i64,
-        id: i64,
+        id: LeaseId,
         options: Option<LeaseGrantOptions>,
     ) -> Result<LeaseGrantResponse> {
         let resp = self
@@ -61,12 +101,12 @@ impl LeaseClient {
     #[inline]
     pub async fn lease_revoke(
         &mut self,
-        id: i64,
+        id: LeaseId,
         options: Option<LeaseRevokeOptions>,
     ) -> Result<LeaseRevokeResponse> {
         let resp = self
             .inner
-            .lease_revoke(options.unwrap_or_default())
+            .lease_revoke(options.unwrap_or_default().with_id(id))
             .await?
             .into_inner();
         Ok(LeaseRevokeResponse::new(resp))
@@ -77,42 +117,37 @@ impl LeaseClient {
     #[inline]
     pub async fn lease_keep_alive(
         &mut self,
-        id: i64,
+        id: LeaseId,
         options: Option<LeaseKeepAliveOptions>,
-    ) -> Result<(i64, i64)> {
-        let (mut sender, receiver) = channel::<PbLeaseKeepAliveRequest>(100);
+    ) -> Result<(LeaseKeeper, LeaseKeepAliveStream)> {
+        let (sender, receiver) = channel::<PbLeaseKeepAliveRequest>(100);
         sender
             .send(options.unwrap_or_default().with_id(id).into())
             .await
             .map_err(|e| Error::WatchError(e.to_string()))?;
-        // TODO: check error
-        /*
-                let mut stream = self.inner.lease_keep_alive(receiver).await?.into_inner();
 
-                let watch_id = match stream.message().await? {
-                    Some(resp) => {
-                        resp.id
-                    }
-                    None => {
-                        return Err(Error::WatchError("failed to create watch".to_string()));
-                    }
-                };
+        let mut stream = self.inner.lease_keep_alive(receiver).await?.into_inner();
 
-                Ok((Watcher::new(id, sender), WatchStream::new(stream)))
-                */
-        Ok((1, 2))
+        let id = match stream.message().await? {
+            Some(resp) => LeaseId::from(resp.id),
+            None => {
+                return Err(Error::WatchError("failed to create lease keeper".to_string()));
+            }
+        };
+
+        Ok((LeaseKeeper::new(id, sender), LeaseKeepAliveStream::new(stream)))
     }
 
     ///lease_time_to_live retrieves lease information.
     pub async fn lease_time_to_live(
         &mut self,
-        id: i64,
+        id: LeaseId,
         keys: bool,
         options: Option<LeaseTimeToLiveOptions>,
     ) -> Result<LeaseTimeToLiveResponse> {
         let resp = self
             .inner
-            .lease_time_to_live(options.unwrap_or_default())
+            .lease_time_to_live(options.unwrap_or_default().with_id(id).with_keys(keys))
             .await?
             .into_inner();
         Ok(LeaseTimeToLiveResponse::new(resp))
@@ -132,6 +167,139 @@ impl LeaseClient {
     }
 }
 
+/// The status of a [`LeaseSession`]'s background keep alive task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeaseSessionStatus {
+    /// The lease is still being kept alive.
+    Alive,
+    /// The keep alive task stopped because the lease was lost, e.g. the server closed the
+    /// stream because the lease expired.
+    Lost(String),
+}
+
+/// A lease that keeps itself alive in the background for as long as it is held.
+///
+/// `LeaseSession` grants a lease and spawns a task which renews it at roughly half its TTL,
+/// so callers don't have to hand-roll a keep alive loop. Dropping the session stops the
+/// background task (without revoking the lease); call [`LeaseSession::revoke`] to also give
+/// the lease back to the server. Call [`LeaseSession::status`] at any time to check whether
+/// the lease has been lost without having to tear the session down.
+pub struct LeaseSession {
+    id: LeaseId,
+    ttl: Arc<AtomicI64>,
+    status: watch::Receiver<LeaseSessionStatus>,
+    close: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<Result<()>>>,
+}
+
+impl LeaseSession {
+    /// Grants a lease with the given ttl (in seconds) and starts keeping it alive.
+    pub async fn new(client: &mut LeaseClient, ttl: i64) -> Result<Self> {
+        let grant = client.lease_grant(ttl, LeaseId::AUTO, None).await?;
+        let id = grant.id();
+        let ttl = Arc::new(AtomicI64::new(grant.ttl()));
+        let (keeper, stream) = client.lease_keep_alive(id, None).await?;
+        let (close_tx, close_rx) = oneshot::channel();
+        let (status_tx, status_rx) = watch::channel(LeaseSessionStatus::Alive);
+        let handle = tokio::spawn(Self::keep_alive_task(
+            keeper,
+            stream,
+            Arc::clone(&ttl),
+            close_rx,
+            status_tx,
+        ));
+
+        Ok(Self {
+            id,
+            ttl,
+            status: status_rx,
+            close: Some(close_tx),
+            handle: Some(handle),
+        })
+    }
+
+    async fn keep_alive_task(
+        mut keeper: LeaseKeeper,
+        mut stream: LeaseKeepAliveStream,
+        ttl: Arc<AtomicI64>,
+        mut close: oneshot::Receiver<()>,
+        status: watch::Sender<LeaseSessionStatus>,
+    ) -> Result<()> {
+        loop {
+            let period = Duration::from_secs((ttl.load(Ordering::Relaxed) / 2).max(1) as u64);
+            tokio::select! {
+                _ = &mut close => return Ok(()),
+                _ = tokio::time::sleep(period) => {
+                    if let Err(e) = keeper.keep_alive().await {
+                        let _ = status.send(LeaseSessionStatus::Lost(e.to_string()));
+                        return Err(e);
+                    }
+                    match stream.message().await {
+                        Ok(Some(resp)) => ttl.store(resp.ttl(), Ordering::Relaxed),
+                        Ok(None) => {
+                            let lost = LeaseSessionStatus::Lost("lease expired".to_string());
+                            let _ = status.send(lost);
+                            return Err(Error::WatchError("lease expired".to_string()));
+                        }
+                        Err(e) => {
+                            let _ = status.send(LeaseSessionStatus::Lost(e.to_string()));
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The ID of the lease kept alive by this session.
+    #[inline]
+    pub const fn lease_id(&self) -> LeaseId {
+        self.id
+    }
+
+    /// The lease's TTL in seconds, as most recently confirmed by a keep alive response (or
+    /// as originally granted, if no keep alive has completed yet).
+    #[inline]
+    pub fn ttl(&self) -> i64 {
+        self.ttl.load(Ordering::Relaxed)
+    }
+
+    /// The current status of the session's background keep alive task.
+    #[inline]
+    pub fn status(&self) -> LeaseSessionStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Stops the background keep alive task and revokes the lease.
+    pub async fn revoke(mut self, client: &mut LeaseClient) -> Result<LeaseRevokeResponse> {
+        self.stop();
+        client.lease_revoke(self.id, None).await
+    }
+
+    /// Stops the background keep alive task, without revoking the lease, and returns its
+    /// result so callers can observe whether the lease was lost while being held.
+    pub async fn close(mut self) -> Result<()> {
+        self.stop();
+        if let Some(handle) = self.handle.take() {
+            handle.await.map_err(|e| Error::WatchError(e.to_string()))??;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(close) = self.close.take() {
+            let _ = close.send(());
+        }
+    }
+}
+
+impl Drop for LeaseSession {
+    #[inline]
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 /// Options for `leaseGrant` operation.
 #[derive(Debug, Default, Clone)]
 #[repr(transparent)]
@@ -146,8 +314,8 @@ impl LeaseGrantOptions {
     }
 
     /// Set id
-    fn with_id(mut self, id: i64) -> Self {
-        self.0.id = id;
+    fn with_id(mut self, id: LeaseId) -> Self {
+        self.0.id = id.into();
         self
     }
 
@@ -207,7 +375,7 @@ impl LeaseGrantResponse {
 
     /// ID is the lease ID for the granted lease.
     #[inline]
-    pub const fn id(&self) -> i64 { self.0.id }
+    pub const fn id(&self) -> LeaseId { LeaseId(self.0.id) }
 
     /// error message if return error.
     #[inline]
@@ -221,8 +389,8 @@ pub struct LeaseRevokeOptions(PbLeaseRevokeRequest);
 
 impl LeaseRevokeOptions {
     /// Set id
-    fn with_id(mut self, id: i64) -> Self {
-        self.0.id = id;
+    fn with_id(mut self, id: LeaseId) -> Self {
+        self.0.id = id.into();
         self
     }
 
@@ -282,8 +450,8 @@ pub struct LeaseKeepAliveOptions(PbLeaseKeepAliveRequest);
 
 impl LeaseKeepAliveOptions {
     /// Set id
-    fn with_id(mut self, id: i64) -> Self {
-        self.0.id = id;
+    fn with_id(mut self, id: LeaseId) -> Self {
+        self.0.id = id.into();
         self
     }
 
@@ -342,7 +510,59 @@ impl LeaseKeepAliveResponse {
 
     /// ID is the lease ID for the keep alive request.
     #[inline]
-    pub const fn id(&self) -> i64 { self.0.id }
+    pub const fn id(&self) -> LeaseId { LeaseId(self.0.id) }
+}
+
+/// The lease keeper keeps a single lease alive by pushing further keep alive requests down
+/// the bidirectional stream opened by `LeaseClient::lease_keep_alive`.
+pub struct LeaseKeeper {
+    id: LeaseId,
+    sender: Sender<PbLeaseKeepAliveRequest>,
+}
+
+impl LeaseKeeper {
+    /// Creates a new `LeaseKeeper`.
+    #[inline]
+    const fn new(id: LeaseId, sender: Sender<PbLeaseKeepAliveRequest>) -> Self {
+        Self { id, sender }
+    }
+
+    /// The lease ID which is being kept alive.
+    #[inline]
+    pub const fn id(&self) -> LeaseId {
+        self.id
+    }
+
+    /// Sends a keep alive request, asking the server to renew the lease's TTL.
+    #[inline]
+    pub async fn keep_alive(&mut self) -> Result<()> {
+        self.sender
+            .send(PbLeaseKeepAliveRequest { id: self.id.into() })
+            .await
+            .map_err(|e| Error::WatchError(e.to_string()))
+    }
+}
+
+/// The lease keep alive stream, used to receive keep alive responses from the server.
+pub struct LeaseKeepAliveStream {
+    stream: Streaming<PbLeaseKeepAliveResponse>,
+}
+
+impl LeaseKeepAliveStream {
+    /// Creates a new `LeaseKeepAliveStream`.
+    #[inline]
+    const fn new(stream: Streaming<PbLeaseKeepAliveResponse>) -> Self {
+        Self { stream }
+    }
+
+    /// Fetches the next keep alive response from the server.
+    #[inline]
+    pub async fn message(&mut self) -> Result<Option<LeaseKeepAliveResponse>> {
+        match self.stream.message().await? {
+            Some(resp) => Ok(Some(LeaseKeepAliveResponse::new(resp))),
+            None => Ok(None),
+        }
+    }
 }
 
 /// Options for `leaseTimeToLive` operation.
@@ -352,8 +572,8 @@ pub struct LeaseTimeToLiveOptions(PbLeaseTimeToLiveRequest);
 
 impl LeaseTimeToLiveOptions {
     /// ID is the lease ID for the lease.
-    fn with_id(mut self, id: i64) -> Self {
-        self.0.id = id;
+    fn with_id(mut self, id: LeaseId) -> Self {
+        self.0.id = id.into();
         self
     }
 
@@ -419,15 +639,17 @@ impl LeaseTimeToLiveResponse {
 
     /// ID is the lease ID from the keep alive request.
     #[inline]
-    pub const fn id(&self) -> i64 { self.0.id }
+    pub const fn id(&self) -> LeaseId { LeaseId(self.0.id) }
 
     /// GrantedTTL is the initial granted time in seconds upon lease creation/renewal.
     #[inline]
     pub const fn grantedTTL(&self) -> i64 { self.0.granted_ttl }
 
-    // Keys is the list of keys attached to this lease.
-    //#[inline]
-    //pub fn keys(&self) -> &[u8] { self.0.keys.as_ref() }
+    /// Keys is the list of keys attached to this lease.
+    #[inline]
+    pub fn keys(&self) -> &[Vec<u8>] {
+        self.0.keys.as_ref()
+    }
 }
 
 /// Options for `leaseLeases` operation.
@@ -480,9 +702,37 @@ impl LeaseLeasesResponse {
         self.0.header.take().map(ResponseHeader::new)
     }
 
-    // get leases status
-    //#[inline]
-    //pub fn take_leases(&self) -> Option<LeaseStatus> {
-    //    self.0.leases.as_ref().take().map(LeaseStatus::new)
-    //}
+    /// The leases currently known to the server.
+    #[inline]
+    pub fn leases(&self) -> Vec<LeaseStatus> {
+        self.0.leases.iter().cloned().map(LeaseStatus::new).collect()
+    }
+
+    /// Takes the leases out of the response, leaving an empty `Vec` in their place.
+    #[inline]
+    pub fn take_leases(&mut self) -> Vec<LeaseStatus> {
+        std::mem::take(&mut self.0.leases)
+            .into_iter()
+            .map(LeaseStatus::new)
+            .collect()
+    }
+}
+
+/// The status of a single lease, as returned by `lease_leases`.
+#[derive(Debug, Clone)]
+#[repr(transparent)]
+pub struct LeaseStatus(PbLeaseStatus);
+
+impl LeaseStatus {
+    /// Creates a new `LeaseStatus` from a pb lease status.
+    #[inline]
+    const fn new(status: PbLeaseStatus) -> Self {
+        Self(status)
+    }
+
+    /// ID is the lease ID.
+    #[inline]
+    pub const fn id(&self) -> LeaseId {
+        LeaseId(self.0.id)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+//! Etcd Lock, a distributed mutex built on leases, watch, and txn.
+
+use crate::error::Result;
+use crate::rpc::kv::{KvClient, PutOptions, RangeOptions};
+use crate::rpc::lease::LeaseId;
+use crate::rpc::txn::{Txn, TxnOp};
+use crate::rpc::watch::{WatchClient, WatchOptions};
+use crate::rpc::ResponseHeader;
+
+/// A distributed mutex built on top of etcd leases, watch, and txn, mirroring the
+/// `/registry`-style lock recipe used by etcd's own `concurrency` packages.
+///
+/// Lock ownership is tied to the caller-supplied lease, so pairing this with a
+/// [`LeaseSession`](crate::rpc::lease::LeaseSession) means a crashed holder's lease
+/// expiration releases the lock automatically.
+pub struct LockClient {
+    kv: KvClient,
+    watch: WatchClient,
+}
+
+impl LockClient {
+    /// Creates a lock client from its underlying kv and watch clients.
+    #[inline]
+    pub fn new(kv: KvClient, watch: WatchClient) -> Self {
+        Self { kv, watch }
+    }
+
+    /// Acquires the lock bound to `name`, blocking until this session is the sole holder.
+    ///
+    /// Creates a key `name/<lease_id>` bound to `lease_id` inside a transaction, then waits
+    /// until that key has the lowest creation revision among all keys sharing the `name/`
+    /// prefix, watching the key that immediately precedes it and re-checking after each
+    /// delete event. The watch starts from the listing's own revision, so a delete that
+    /// races between the `range` and the `watch` RPC is still observed.
+    pub async fn lock(&mut self, name: &[u8], lease_id: LeaseId) -> Result<LockResponse> {
+        let mut prefix = name.to_vec();
+        prefix.push(b'/');
+        let mut key = prefix.clone();
+        key.extend_from_slice(lease_id.to_string().as_bytes());
+
+        let put = TxnOp::put(
+            key.clone(),
+            Vec::new(),
+            Some(PutOptions::new().with_lease(lease_id.into())),
+        );
+        let txn_resp = self.kv.txn(Txn::new().and_then(vec![put])).await?;
+        let create_revision = txn_resp.header().map_or(0, ResponseHeader::revision);
+
+        loop {
+            let range = self
+                .kv
+                .range(prefix.clone(), Some(RangeOptions::new().with_prefix()))
+                .await?;
+            let list_revision = range.header().map_or(0, ResponseHeader::revision);
+
+            let entries: Vec<(i64, Vec<u8>)> = range
+                .kvs()
+                .iter()
+                .map(|kv| (kv.create_revision(), kv.key().to_vec()))
+                .collect();
+
+            let preceding_key = match preceding_key(&entries, create_revision) {
+                Some(key) => key,
+                None => break,
+            };
+
+            let (_watcher, mut stream) = self
+                .watch
+                .watch(
+                    preceding_key,
+                    Some(WatchOptions::new().with_start_revision(list_revision)),
+                )
+                .await?;
+            while let Some(resp) = stream.message().await? {
+                if resp.events().iter().any(|event| event.is_delete()) {
+                    break;
+                }
+            }
+        }
+
+        Ok(LockResponse::new(key))
+    }
+
+    /// Releases a lock previously acquired with [`LockClient::lock`].
+    pub async fn unlock(&mut self, key: Vec<u8>) -> Result<UnlockResponse> {
+        self.kv.delete(key, None).await?;
+        Ok(UnlockResponse::new())
+    }
+}
+
+/// Returns the key among `entries` whose creation revision immediately precedes
+/// `create_revision`, or `None` if `create_revision` is already the lowest of them all —
+/// meaning the caller holds the lock.
+fn preceding_key(entries: &[(i64, Vec<u8>)], create_revision: i64) -> Option<Vec<u8>> {
+    entries
+        .iter()
+        .filter(|(rev, _)| *rev < create_revision)
+        .max_by_key(|(rev, _)| *rev)
+        .map(|(_, key)| key.clone())
+}
+
+/// Response for `lock` operation.
+#[derive(Debug, Clone)]
+pub struct LockResponse {
+    key: Vec<u8>,
+}
+
+impl LockResponse {
+    /// Creates a new `LockResponse`.
+    #[inline]
+    const fn new(key: Vec<u8>) -> Self {
+        Self { key }
+    }
+
+    /// The key holding the lock; pass it to [`LockClient::unlock`] to release it.
+    #[inline]
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+/// Response for `unlock` operation.
+#[derive(Debug, Clone, Default)]
+pub struct UnlockResponse;
+
+impl UnlockResponse {
+    /// Creates a new `UnlockResponse`.
+    #[inline]
+    const fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::preceding_key;
+
+    #[test]
+    fn lowest_revision_holds_the_lock() {
+        let entries = vec![(5, b"name/1".to_vec()), (7, b"name/2".to_vec())];
+        assert_eq!(preceding_key(&entries, 5), None);
+    }
+
+    #[test]
+    fn non_lowest_revision_watches_its_immediate_predecessor() {
+        let entries = vec![
+            (3, b"name/1".to_vec()),
+            (5, b"name/2".to_vec()),
+            (9, b"name/3".to_vec()),
+        ];
+        assert_eq!(preceding_key(&entries, 9), Some(b"name/2".to_vec()));
+    }
+
+    #[test]
+    fn rechecks_after_predecessor_is_deleted() {
+        let mut entries = vec![
+            (3, b"name/1".to_vec()),
+            (5, b"name/2".to_vec()),
+            (9, b"name/3".to_vec()),
+        ];
+        assert_eq!(preceding_key(&entries, 9), Some(b"name/2".to_vec()));
+
+        // The immediate predecessor (revision 5) is deleted; the next re-check should now
+        // watch the one before it.
+        entries.retain(|(rev, _)| *rev != 5);
+        assert_eq!(preceding_key(&entries, 9), Some(b"name/1".to_vec()));
+
+        // Once every predecessor is gone, the caller holds the lock.
+        entries.retain(|(rev, _)| *rev != 3);
+        assert_eq!(preceding_key(&entries, 9), None);
+    }
+}